@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{de, Error};
+
+/// A zero-copy counterpart to [`Deserializer`](crate::Deserializer): borrows its keys and
+/// values from the input instead of cloning them.
+pub type BorrowedDeserializer<'de> = de::QueryDeserializer<&'de str>;
+
+impl<'de> BorrowedDeserializer<'de> {
+    pub fn from_key_values(input: &'de [(String, String)]) -> Self {
+        let mut key_values = BTreeMap::<_, VecDeque<&'de str>>::new();
+
+        for (k, v) in input {
+            key_values
+                .entry(k.as_str())
+                .or_default()
+                .push_back(v.as_str());
+        }
+
+        Self::new(key_values)
+    }
+}
+
+/// Deserializes `T` from a `&'de [(String, String)]`, borrowing `&str` fields directly out of
+/// the input rather than cloning them. For types that need to own their data, use
+/// [`from_key_values`](crate::from_key_values) instead.
+pub fn from_borrowed_key_values<'de, T>(key_values: &'de [(String, String)]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = BorrowedDeserializer::from_key_values(key_values);
+    let t = T::deserialize(&mut deserializer)?;
+    if deserializer.key_values.is_empty() {
+        Ok(t)
+    } else {
+        Err(Error::TrailingValues)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn borrowed_single_array() {
+        #[derive(Debug, Deserialize)]
+        pub struct IdVec<'q> {
+            #[serde(borrow)]
+            id: Vec<&'q str>,
+        }
+
+        let pairs = vec![
+            ("id".to_string(), "1".to_string()),
+            ("id".to_string(), "2".to_string()),
+            ("id".to_string(), "3".to_string()),
+        ];
+
+        let ids: IdVec = from_borrowed_key_values(&pairs).unwrap();
+        assert_eq!(ids.id, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn borrowed_array_and_number() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query<'q> {
+            #[serde(borrow)]
+            id: Vec<&'q str>,
+            foo: u32,
+        }
+
+        let pairs = vec![
+            ("id".to_string(), "1".to_string()),
+            ("id".to_string(), "2".to_string()),
+            ("foo".to_string(), "3".to_string()),
+        ];
+
+        let query: Query = from_borrowed_key_values(&pairs).unwrap();
+        assert_eq!(query.id, vec!["1", "2"]);
+        assert_eq!(query.foo, 3);
+    }
+
+    #[test]
+    fn borrowed_simple_enum() {
+        #[derive(Debug, Eq, PartialEq, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum MyEnum {
+            A,
+            B,
+            C,
+        }
+
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            id: Vec<MyEnum>,
+            foo: MyEnum,
+        }
+
+        let pairs = vec![
+            ("id".to_string(), "a".to_string()),
+            ("id".to_string(), "b".to_string()),
+            ("foo".to_string(), "c".to_string()),
+        ];
+
+        let query: Query = from_borrowed_key_values(&pairs).unwrap();
+        assert_eq!(query.id, vec![MyEnum::A, MyEnum::B]);
+        assert_eq!(query.foo, MyEnum::C);
+    }
+
+    #[test]
+    fn borrowed_u128_array() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            balance: Vec<u128>,
+        }
+
+        let pairs = vec![
+            ("balance".to_string(), "0".to_string()),
+            (
+                "balance".to_string(),
+                "340282366920938463463374607431768211455".to_string(),
+            ),
+        ];
+
+        let query: Query = from_borrowed_key_values(&pairs).unwrap();
+        assert_eq!(query.balance, vec![0, u128::MAX]);
+    }
+
+    #[test]
+    fn borrowed_char_field() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            grade: char,
+        }
+
+        let pairs = vec![("grade".to_string(), "A".to_string())];
+        let query: Query = from_borrowed_key_values(&pairs).unwrap();
+        assert_eq!(query.grade, 'A');
+    }
+
+    #[test]
+    fn borrowed_char_field_rejects_multiple_characters() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            #[allow(dead_code)]
+            grade: char,
+        }
+
+        let pairs = vec![("grade".to_string(), "AB".to_string())];
+        let err = from_borrowed_key_values::<Query>(&pairs).unwrap_err();
+        assert!(matches!(err, Error::Message(_)));
+    }
+}
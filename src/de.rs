@@ -0,0 +1,421 @@
+use serde::{
+    de::{
+        self, value::BorrowedStrDeserializer, DeserializeSeed, IntoDeserializer, MapAccess,
+        SeqAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::Error;
+
+// Copied from serde_urlencoded and modified
+macro_rules! forward_parsed_value {
+    ($($ty:ident => $method:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+                where V: de::Visitor<'de>
+            {
+                match self.next_unit()?.as_ref().parse::<$ty>() {
+                    Ok(val) => val.into_deserializer().$method(visitor),
+                    Err(e) => Err(de::Error::custom(e))
+                }
+            }
+        )*
+    }
+}
+
+/// How a query-string key or value reaches a serde `Visitor`: cloned into an
+/// owned `String` by [`crate::Deserializer`], or borrowed zero-copy out of
+/// the input by [`crate::BorrowedDeserializer`]. Factoring this out is what
+/// lets [`QueryDeserializer`] implement the traversal logic once for both.
+pub(crate) trait QueryStr<'de>: Ord + Clone + AsRef<str> {
+    fn visit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>;
+
+    /// Deserialize `self` as an enum variant/tag name for `seed`, preserving
+    /// each mode's own zero-copy guarantees.
+    fn deserialize_variant_name<V>(self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>;
+}
+
+impl<'de> QueryStr<'de> for String {
+    fn visit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self)
+    }
+
+    fn deserialize_variant_name<V>(self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.into_deserializer())
+    }
+}
+
+impl<'de> QueryStr<'de> for &'de str {
+    fn visit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self)
+    }
+
+    fn deserialize_variant_name<V>(self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(BorrowedStrDeserializer::new(self))
+    }
+}
+
+/// The state machine shared by [`crate::Deserializer`] (owned `String` keys
+/// and values) and [`crate::BorrowedDeserializer`] (borrowed `&'de str` keys
+/// and values). Generic over the key/value representation `K` so a fix to
+/// the map/sequence traversal lands in both at once instead of risking the
+/// two copies silently drifting apart.
+#[derive(Debug)]
+pub(crate) struct QueryDeserializer<K> {
+    pub(crate) key_values: BTreeMap<K, VecDeque<K>>,
+    in_map: bool,
+    in_sequence: bool,
+    // Set for the duration of a `MapAccess::next_key_seed` call, so that
+    // `deserialize_any`/`deserialize_identifier` know to describe the
+    // current *key* rather than the current key's value. Needed because
+    // serde's `Content` buffering (used by internally tagged enums)
+    // deserializes a map's keys through `deserialize_any`, and an internally
+    // tagged enum's tag *value* through `deserialize_identifier`, rather
+    // than always going through `deserialize_string`.
+    reading_key: bool,
+}
+
+impl<K: Ord> QueryDeserializer<K> {
+    pub(crate) fn new(key_values: BTreeMap<K, VecDeque<K>>) -> Self {
+        Self {
+            key_values,
+            in_map: false,
+            in_sequence: false,
+            reading_key: false,
+        }
+    }
+
+    /// Return the next key to be read by the visitor.
+    fn current_key(&self) -> Result<K, Error>
+    where
+        K: Clone,
+    {
+        self.key_values
+            .keys()
+            .next()
+            .cloned()
+            .ok_or(Error::MissingKey)
+    }
+
+    fn current_values(&mut self) -> Result<&mut VecDeque<K>, Error> {
+        self.key_values
+            .values_mut()
+            .next()
+            .ok_or(Error::MissingValues)
+    }
+
+    fn next_unit(&mut self) -> Result<K, Error>
+    where
+        K: Clone + AsRef<str>,
+    {
+        let values = self.current_values()?;
+        let value = values.pop_front().ok_or(Error::MissingValue)?;
+
+        if values.is_empty() {
+            let key = self.current_key()?;
+            self.key_values
+                .remove(&key)
+                .ok_or_else(|| Error::RemoveKeyFailed(key.as_ref().to_string()))?;
+            self.in_sequence = false;
+        }
+
+        Ok(value)
+    }
+}
+
+impl<'de, 'a, K> MapAccess<'de> for &'a mut QueryDeserializer<K>
+where
+    K: QueryStr<'de>,
+{
+    type Error = Error;
+
+    fn next_key_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        if self.key_values.is_empty() {
+            Ok(None)
+        } else {
+            self.reading_key = true;
+            let result = seed.deserialize(&mut **self);
+            self.reading_key = false;
+            result.map(Some)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut **self)
+    }
+}
+
+impl<'de, 'a, K> SeqAccess<'de> for &'a mut QueryDeserializer<K>
+where
+    K: QueryStr<'de>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if !self.in_sequence {
+            Ok(None)
+        } else {
+            seed.deserialize(&mut **self).map(Some)
+        }
+    }
+}
+
+impl<'de, 'a, K> de::Deserializer<'de> for &'a mut QueryDeserializer<K>
+where
+    K: QueryStr<'de>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Query values are always strings, so driving the visitor with the
+        // real shape of the data (a map at the top level, otherwise a
+        // sequence or a single string) is enough for serde's `Content`
+        // buffering (used by untagged and internally tagged enums) to
+        // capture the value. Note that `Content` has no way to turn a
+        // captured string back into a number or bool, so an untagged enum
+        // with a non-string variant will fall through to a string variant
+        // instead of matching the numeric one; see the `untagged_enum_*`
+        // tests.
+        if !self.in_map {
+            return self.deserialize_map(visitor);
+        }
+
+        if self.reading_key {
+            return self.current_key()?.visit(visitor);
+        }
+
+        if !self.in_sequence && self.current_values()?.len() > 1 {
+            self.deserialize_seq(visitor)
+        } else {
+            self.deserialize_string(visitor)
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // `#[serde(flatten)]` fields don't reach this guard: serde buffers the
+        // top-level map's unknown keys and their values into `Content` (the
+        // values through `deserialize_any`, which never opens a map while
+        // `in_map` is set) and deserializes the flattened target from that
+        // buffer via its own `FlatMapDeserializer`, which never calls back
+        // into this `Deserializer`. So the only way to actually hit
+        // `ForbiddenNestedMap` is a struct or map as a field's value (e.g.
+        // `x=y=1&y=2` in the `no_nested_map` test in `lib.rs`), which is still
+        // correctly rejected. There is no guard to relax here for flatten.
+        if self.in_map {
+            return Err(Error::ForbiddenNestedMap);
+        }
+
+        self.in_map = true;
+        let result = visitor.visit_map(&mut self)?;
+        self.in_map = false;
+
+        Ok(result)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Most calls here are reading a struct field name, while
+        // `reading_key` is set. But an internally tagged enum also reaches
+        // this method to read the tag's *value* (e.g. `"Circle"` in
+        // `type=Circle&radius=5`) against the known variant names, in which
+        // case it must behave like `deserialize_string` instead.
+        if self.reading_key {
+            self.current_key()?.visit(visitor)
+        } else {
+            self.deserialize_string(visitor)
+        }
+    }
+
+    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Disallow sequences within sequences for simplicity.
+        if self.in_sequence {
+            return Err(Error::ForbiddenNestedSequence);
+        }
+
+        self.in_sequence = true;
+        let result = visitor.visit_seq(&mut self)?;
+
+        // The `in_sequence` bool should be switched off after reading all elements.
+        if self.in_sequence {
+            return Err(Error::SequenceNotConsumed);
+        }
+
+        Ok(result)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.next_unit()?.visit(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.next_unit()?.visit(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.in_map {
+            return Err(Error::ForbiddenTopLevelOption);
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let value = self.next_unit()?;
+        visitor.visit_enum(EnumAccess(value))
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = self.next_unit()?;
+        let mut chars = value.as_ref().chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::Message(format!(
+                "expected a single character, found {:?}",
+                value.as_ref()
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct newtype_struct tuple
+        tuple_struct ignored_any
+    }
+
+    forward_parsed_value! {
+        bool => deserialize_bool,
+        u8 => deserialize_u8,
+        u16 => deserialize_u16,
+        u32 => deserialize_u32,
+        u64 => deserialize_u64,
+        u128 => deserialize_u128,
+        i8 => deserialize_i8,
+        i16 => deserialize_i16,
+        i32 => deserialize_i32,
+        i64 => deserialize_i64,
+        i128 => deserialize_i128,
+        f32 => deserialize_f32,
+        f64 => deserialize_f64,
+    }
+}
+
+struct EnumAccess<K>(K);
+
+impl<'de, K> de::EnumAccess<'de> for EnumAccess<K>
+where
+    K: QueryStr<'de>,
+{
+    type Error = Error;
+    type Variant = UnitOnlyVariantAccess;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = self.0.deserialize_variant_name(seed)?;
+        Ok((variant, UnitOnlyVariantAccess))
+    }
+}
+
+struct UnitOnlyVariantAccess;
+
+impl<'de> de::VariantAccess<'de> for UnitOnlyVariantAccess {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::ExpectedUnitVariant)
+    }
+}
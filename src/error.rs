@@ -1,8 +1,10 @@
-use serde::de;
+use serde::{de, ser};
 use std::fmt::{self, Display};
 
 #[cfg(feature = "from_str")]
 use serde_urlencoded::de::Error as UrlEncodedError;
+#[cfg(feature = "from_str")]
+use serde_urlencoded::ser::Error as UrlEncodedSerError;
 
 #[derive(Debug)]
 pub enum Error {
@@ -16,9 +18,12 @@ pub enum Error {
     ForbiddenTopLevelOption,
     ExpectedUnitVariant,
     RemoveKeyFailed(String),
+    Unsupported(&'static str),
     Message(String),
     #[cfg(feature = "from_str")]
     UrlEncoded(UrlEncodedError),
+    #[cfg(feature = "from_str")]
+    UrlEncodedSer(UrlEncodedSerError),
 }
 
 impl de::Error for Error {
@@ -27,6 +32,12 @@ impl de::Error for Error {
     }
 }
 
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
 #[cfg(feature = "from_str")]
 impl From<UrlEncodedError> for Error {
     fn from(e: UrlEncodedError) -> Self {
@@ -34,6 +45,13 @@ impl From<UrlEncodedError> for Error {
     }
 }
 
+#[cfg(feature = "from_str")]
+impl From<UrlEncodedSerError> for Error {
+    fn from(e: UrlEncodedSerError) -> Self {
+        Self::UrlEncodedSer(e)
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -1,35 +1,21 @@
-use serde::{
-    de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor},
-    forward_to_deserialize_any, Deserialize,
-};
+use serde::Deserialize;
 use std::collections::{BTreeMap, VecDeque};
 
+mod borrowed;
+mod de;
 mod error;
+mod ser;
 
+pub use borrowed::{from_borrowed_key_values, BorrowedDeserializer};
 pub use error::Error;
+pub use ser::Serializer;
+pub use ser::to_key_values;
+#[cfg(feature = "from_str")]
+pub use ser::to_string;
 
-// Copied from serde_urlencoded and modified
-macro_rules! forward_parsed_value {
-    ($($ty:ident => $method:ident,)*) => {
-        $(
-            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-                where V: de::Visitor<'de>
-            {
-                match self.next_unit()?.as_str().parse::<$ty>() {
-                    Ok(val) => val.into_deserializer().$method(visitor),
-                    Err(e) => Err(de::Error::custom(e))
-                }
-            }
-        )*
-    }
-}
-
-#[derive(Debug)]
-pub struct Deserializer {
-    key_values: BTreeMap<String, VecDeque<String>>,
-    in_map: bool,
-    in_sequence: bool,
-}
+/// Deserializes a `T` from a `Vec<(String, String)>`, cloning keys and values as needed. For a
+/// zero-copy alternative, see [`BorrowedDeserializer`].
+pub type Deserializer = de::QueryDeserializer<String>;
 
 impl Deserializer {
     pub fn from_key_values(input: Vec<(String, String)>) -> Self {
@@ -39,43 +25,7 @@ impl Deserializer {
             key_values.entry(k).or_default().push_back(v);
         }
 
-        Self {
-            key_values,
-            in_map: false,
-            in_sequence: false,
-        }
-    }
-
-    /// Return the next key to be read by the visitor.
-    fn current_key(&self) -> Result<String, Error> {
-        // TODO: could maybe avoid the clone here if we fiddle with deserializer lifetimes
-        self.key_values
-            .keys()
-            .next()
-            .cloned()
-            .ok_or(Error::MissingKey)
-    }
-
-    fn current_values(&mut self) -> Result<&mut VecDeque<String>, Error> {
-        self.key_values
-            .values_mut()
-            .next()
-            .ok_or(Error::MissingValues)
-    }
-
-    fn next_unit(&mut self) -> Result<String, Error> {
-        let values = self.current_values()?;
-        let value = values.pop_front().ok_or(Error::MissingValue)?;
-
-        if values.is_empty() {
-            let key = self.current_key()?;
-            self.key_values
-                .remove(&key)
-                .ok_or_else(|| Error::RemoveKeyFailed(key))?;
-            self.in_sequence = false;
-        }
-
-        Ok(value)
+        Self::new(key_values)
     }
 }
 
@@ -100,226 +50,6 @@ where
     }
 }
 
-impl<'de, 'a> MapAccess<'de> for &'a mut Deserializer {
-    type Error = Error;
-
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
-    where
-        K: DeserializeSeed<'de>,
-    {
-        if self.key_values.is_empty() {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut **self).map(Some)
-        }
-    }
-
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
-    where
-        V: DeserializeSeed<'de>,
-    {
-        seed.deserialize(&mut **self)
-    }
-}
-
-impl<'de, 'a> SeqAccess<'de> for &'a mut Deserializer {
-    type Error = Error;
-
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        if !self.in_sequence {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut **self).map(Some)
-        }
-    }
-}
-
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer {
-    type Error = Error;
-
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        self.deserialize_string(visitor)
-    }
-
-    fn deserialize_struct<V>(
-        self,
-        _name: &'static str,
-        _fields: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        self.deserialize_map(visitor)
-    }
-
-    fn deserialize_map<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        if self.in_map {
-            return Err(Error::ForbiddenNestedMap);
-        }
-
-        self.in_map = true;
-        let result = visitor.visit_map(&mut self)?;
-        self.in_map = false;
-
-        Ok(result)
-    }
-
-    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        let key = self.current_key()?;
-        visitor.visit_string(key)
-    }
-
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        // Disallow sequences within sequences for simplicity.
-        if self.in_sequence {
-            return Err(Error::ForbiddenNestedSequence);
-        }
-
-        self.in_sequence = true;
-        let result = visitor.visit_seq(&mut self)?;
-
-        // The `in_sequence` bool should be switched off after reading all elements.
-        if self.in_sequence {
-            return Err(Error::SequenceNotConsumed);
-        }
-
-        Ok(result)
-    }
-
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        let value = self.next_unit()?;
-        visitor.visit_string(value)
-    }
-
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        if !self.in_map {
-            return Err(Error::ForbiddenTopLevelOption);
-        }
-        visitor.visit_some(self)
-    }
-
-    fn deserialize_enum<V>(
-        self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        let value = self.next_unit()?;
-        visitor.visit_enum(EnumAccess(value))
-    }
-
-    forward_to_deserialize_any! {
-        char str
-        bytes byte_buf unit unit_struct newtype_struct tuple
-        tuple_struct ignored_any
-    }
-
-    forward_parsed_value! {
-        bool => deserialize_bool,
-        u8 => deserialize_u8,
-        u16 => deserialize_u16,
-        u32 => deserialize_u32,
-        u64 => deserialize_u64,
-        i8 => deserialize_i8,
-        i16 => deserialize_i16,
-        i32 => deserialize_i32,
-        i64 => deserialize_i64,
-        f32 => deserialize_f32,
-        f64 => deserialize_f64,
-    }
-}
-
-struct EnumAccess(String);
-
-impl<'de, 'a> de::EnumAccess<'de> for EnumAccess {
-    type Error = Error;
-    type Variant = UnitOnlyVariantAccess;
-
-    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
-    where
-        V: de::DeserializeSeed<'de>,
-    {
-        let variant = seed.deserialize::<de::value::StringDeserializer<Self::Error>>(
-            self.0.into_deserializer(),
-        )?;
-        Ok((variant, UnitOnlyVariantAccess))
-    }
-}
-
-struct UnitOnlyVariantAccess;
-
-impl<'de, 'a> de::VariantAccess<'de> for UnitOnlyVariantAccess {
-    type Error = Error;
-
-    fn unit_variant(self) -> Result<(), Self::Error> {
-        Ok(())
-    }
-
-    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value, Self::Error>
-    where
-        T: de::DeserializeSeed<'de>,
-    {
-        Err(Error::ExpectedUnitVariant)
-    }
-
-    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Error::ExpectedUnitVariant)
-    }
-
-    fn struct_variant<V>(
-        self,
-        _fields: &'static [&'static str],
-        _visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Error::ExpectedUnitVariant)
-    }
-
-    forward_parsed_value! {
-        bool => deserialize_bool,
-        u8 => deserialize_u8,
-        u16 => deserialize_u16,
-        u32 => deserialize_u32,
-        u64 => deserialize_u64,
-        i8 => deserialize_i8,
-        i16 => deserialize_i16,
-        i32 => deserialize_i32,
-        i64 => deserialize_i64,
-        f32 => deserialize_f32,
-        f64 => deserialize_f64,
-    }
-}
-
 #[cfg(test)]
 mod test {
     use std::cmp::{Eq, Ord, PartialEq, PartialOrd};
@@ -541,4 +271,120 @@ mod test {
         assert_eq!(ids.id, vec![MyEnum::A, MyEnum::B, MyEnum::C, MyEnum::B]);
         assert_eq!(ids.foo, MyEnum::C);
     }
+
+    #[test]
+    fn flatten_extra_fields() {
+        use std::collections::HashMap;
+
+        #[derive(Debug, Deserialize)]
+        pub struct Example {
+            id: String,
+            #[serde(flatten)]
+            extra: HashMap<String, String>,
+        }
+
+        let q = "id=5&foo=bar&baz=qux";
+        let v: Example = from_str(q).unwrap();
+
+        assert_eq!(v.id, "5");
+        assert_eq!(v.extra.get("foo").map(String::as_str), Some("bar"));
+        assert_eq!(v.extra.get("baz").map(String::as_str), Some("qux"));
+    }
+
+    #[test]
+    fn untagged_enum() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        pub enum Value {
+            List(Vec<String>),
+            Single(String),
+        }
+
+        #[derive(Debug, Deserialize)]
+        pub struct Example {
+            x: Value,
+        }
+
+        let v: Example = from_str("x=hello").unwrap();
+        assert_eq!(v.x, Value::Single("hello".into()));
+
+        let v: Example = from_str("x=a&x=b").unwrap();
+        assert_eq!(v.x, Value::List(vec!["a".into(), "b".into()]));
+    }
+
+    #[test]
+    fn untagged_enum_numeric_variant_falls_back_to_string() {
+        // `Content` buffering has no way to turn a captured string back into
+        // a number, so the numeric variant never matches and the untagged
+        // enum falls back to the string variant. This is pinned down as a
+        // known limitation rather than left to regress silently further.
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        pub enum Value {
+            Number(i32),
+            Text(String),
+        }
+
+        #[derive(Debug, Deserialize)]
+        pub struct Example {
+            x: Value,
+        }
+
+        let v: Example = from_str("x=5").unwrap();
+        assert_eq!(v.x, Value::Text("5".into()));
+    }
+
+    #[test]
+    fn internally_tagged_enum() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(tag = "type")]
+        pub enum Shape {
+            Circle { radius: String },
+            Square { side: String },
+        }
+
+        let v: Shape = from_str("type=Circle&radius=5").unwrap();
+        assert_eq!(
+            v,
+            Shape::Circle {
+                radius: "5".into()
+            }
+        );
+    }
+
+    #[test]
+    fn u128_array() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            balance: Vec<u128>,
+        }
+
+        let q = "balance=0&balance=340282366920938463463374607431768211455";
+        let query: Query = from_str(q).unwrap();
+
+        assert_eq!(query.balance, vec![0, u128::MAX]);
+    }
+
+    #[test]
+    fn char_field() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            grade: char,
+        }
+
+        let query: Query = from_str("grade=A").unwrap();
+        assert_eq!(query.grade, 'A');
+    }
+
+    #[test]
+    fn char_field_rejects_multiple_characters() {
+        #[derive(Debug, Deserialize)]
+        pub struct Query {
+            #[allow(dead_code)]
+            grade: char,
+        }
+
+        let err = from_str::<Query>("grade=AB").unwrap_err();
+        assert!(matches!(err, Error::Message(_)));
+    }
 }
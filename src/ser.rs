@@ -0,0 +1,728 @@
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::Error;
+
+/// Serializes a value into the `Vec<(String, String)>` form consumed by
+/// [`from_key_values`](crate::from_key_values), using exactly the inverse
+/// semantics of [`Deserializer`](crate::Deserializer).
+#[derive(Debug, Default)]
+pub struct Serializer {
+    pairs: Vec<(String, String)>,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn into_pairs(self) -> Vec<(String, String)> {
+        self.pairs
+    }
+}
+
+pub fn to_key_values<T>(value: &T) -> Result<Vec<(String, String)>, Error>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::new();
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_pairs())
+}
+
+#[cfg(feature = "from_str")]
+pub fn to_string<T>(value: &T) -> Result<String, Error>
+where
+    T: Serialize,
+{
+    Ok(serde_urlencoded::to_string(to_key_values(value)?)?)
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = StructSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(Error::ForbiddenTopLevelOption)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::ForbiddenTopLevelOption)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(Error::Unsupported("expected a struct or map at the top level"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(StructSerializer {
+            pairs: &mut self.pairs,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(StructSerializer {
+            pairs: &mut self.pairs,
+            key: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(Error::ExpectedUnitVariant)
+    }
+}
+
+/// Serializes the fields of a top-level struct or map, one `(key, value)`
+/// pair (or, for a `Vec` field, several) at a time.
+pub struct StructSerializer<'a> {
+    pairs: &'a mut Vec<(String, String)>,
+    key: Option<String>,
+}
+
+impl<'a> ser::SerializeStruct for StructSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(ValueSerializer {
+            key,
+            pairs: self.pairs,
+            in_sequence: false,
+        })
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for StructSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.key.take().ok_or(Error::MissingKey)?;
+        value.serialize(ValueSerializer {
+            key: &key,
+            pairs: self.pairs,
+            in_sequence: false,
+        })
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+}
+
+/// Serializes a map key into the `String` form that `Deserializer` keys use.
+struct KeySerializer;
+
+macro_rules! serialize_key_via_display {
+    ($($method:ident($ty:ty),)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Self::Ok, Self::Error> {
+                Ok(v.to_string())
+            }
+        )*
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    serialize_key_via_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(Error::Unsupported("map keys must be string-like"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(Error::ExpectedUnitVariant)
+    }
+}
+
+/// Serializes a single field's value, turning a `Vec<T>` into one repeated
+/// `(key, value)` pair per element and omitting the key for `None`.
+struct ValueSerializer<'a> {
+    key: &'a str,
+    pairs: &'a mut Vec<(String, String)>,
+    in_sequence: bool,
+}
+
+macro_rules! serialize_scalar_via_display {
+    ($($method:ident($ty:ty),)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Self::Ok, Self::Error> {
+                self.pairs.push((self.key.to_string(), v.to_string()));
+                Ok(())
+            }
+        )*
+    }
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    serialize_scalar_via_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.pairs.push((self.key.to_string(), v.to_string()));
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        let value = String::from_utf8(v.to_vec())
+            .map_err(|e| Error::Message(format!("invalid utf-8 in byte value: {e}")))?;
+        self.pairs.push((self.key.to_string(), value));
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.pairs.push((self.key.to_string(), String::new()));
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.pairs.push((self.key.to_string(), variant.to_string()));
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        if self.in_sequence {
+            return Err(Error::ForbiddenNestedSequence);
+        }
+
+        Ok(SeqSerializer {
+            key: self.key,
+            pairs: self.pairs,
+        })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(Error::Unsupported("tuples are not supported"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(Error::Unsupported("tuples are not supported"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(Error::ExpectedUnitVariant)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(Error::ForbiddenNestedMap)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(Error::ForbiddenNestedMap)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(Error::ExpectedUnitVariant)
+    }
+}
+
+/// Serializes the elements of a `Vec<T>` field into repeated `(key, value)`
+/// pairs sharing the field's key, matching the `id=1&id=2&id=3` input form.
+struct SeqSerializer<'a> {
+    key: &'a str,
+    pairs: &'a mut Vec<(String, String)>,
+}
+
+impl<'a> ser::SerializeSeq for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(ValueSerializer {
+            key: self.key,
+            pairs: self.pairs,
+            in_sequence: true,
+        })
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::from_str;
+    use serde::Serialize;
+
+    #[test]
+    fn single_array_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        pub struct IdVec {
+            id: Vec<String>,
+        }
+
+        let value = IdVec {
+            id: vec!["1".into(), "2".into(), "3".into()],
+        };
+
+        let pairs = to_key_values(&value).unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("id".to_string(), "1".to_string()),
+                ("id".to_string(), "2".to_string()),
+                ("id".to_string(), "3".to_string()),
+            ]
+        );
+
+        let round_tripped: IdVec = crate::from_key_values(pairs).unwrap();
+        assert_eq!(round_tripped, value);
+    }
+
+    #[test]
+    fn array_and_number_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        pub struct Query {
+            id: Vec<String>,
+            foo: u32,
+        }
+
+        let value = Query {
+            id: vec!["1".into(), "2".into()],
+            foo: 3,
+        };
+
+        let s = to_string(&value).unwrap();
+        let round_tripped: Query = from_str(&s).unwrap();
+        assert_eq!(round_tripped, value);
+    }
+
+    #[test]
+    fn simple_enum_round_trip() {
+        #[derive(Debug, Eq, PartialEq, Serialize, serde::Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        pub enum MyEnum {
+            A,
+            B,
+            C,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        pub struct Query {
+            id: Vec<MyEnum>,
+            foo: MyEnum,
+        }
+
+        let value = Query {
+            id: vec![MyEnum::A, MyEnum::B, MyEnum::C, MyEnum::B],
+            foo: MyEnum::C,
+        };
+
+        let s = to_string(&value).unwrap();
+        let round_tripped: Query = from_str(&s).unwrap();
+        assert_eq!(round_tripped, value);
+    }
+
+    #[test]
+    fn option_none_omits_key() {
+        #[derive(Debug, Serialize)]
+        pub struct Example {
+            x: Option<String>,
+            y: String,
+        }
+
+        let value = Example {
+            x: None,
+            y: "5".into(),
+        };
+
+        let pairs = to_key_values(&value).unwrap();
+        assert_eq!(pairs, vec![("y".to_string(), "5".to_string())]);
+    }
+
+    #[test]
+    fn nested_map_forbidden() {
+        #[derive(Debug, Serialize)]
+        pub struct L1 {
+            y: Vec<String>,
+        }
+
+        #[derive(Debug, Serialize)]
+        pub struct L2 {
+            x: L1,
+        }
+
+        let value = L2 {
+            x: L1 { y: vec!["1".into()] },
+        };
+
+        let err = to_key_values(&value).unwrap_err();
+        assert!(matches!(err, Error::ForbiddenNestedMap));
+    }
+}